@@ -0,0 +1,81 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use xz2::stream::{Check, Filters, LzmaOptions, Stream};
+use xz2::write::XzEncoder;
+
+/// Compression backend for [`archive_directory`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Xz,
+}
+
+/// `xz` compression effort, mirroring the level/`-e` "extreme" knobs that
+/// installer tooling (e.g. `xz -9e`) exposes to trade CPU time for a
+/// smaller archive. Ignored when the chosen [`Format`] is `Gzip`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XzLevel {
+    pub preset: u32,
+    pub extreme: bool,
+}
+
+impl XzLevel {
+    /// Highest compression ratio `xz` offers, at the cost of being the
+    /// slowest and most memory-hungry preset.
+    pub const BEST: XzLevel = XzLevel {
+        preset: 9,
+        extreme: true,
+    };
+}
+
+/// `LZMA_PRESET_EXTREME` from liblzma: OR'd into a preset level to select
+/// the higher-ratio, slower "extreme" variant of that level (`xz -9e`).
+const LZMA_PRESET_EXTREME: u32 = 1 << 31;
+
+/// Walks `source_dir`, writes it as a tar stream and pipes that stream
+/// through the chosen compressor into `out_path`.
+///
+/// Returns the path to the written archive.
+pub fn archive_directory(
+    source_dir: &str,
+    out_path: &str,
+    format: Format,
+    xz_level: XzLevel,
+) -> io::Result<PathBuf> {
+    let base_name = Path::new(source_dir).file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "source_dir has no file name")
+    })?;
+    let writer = BufWriter::new(File::create(out_path)?);
+    match format {
+        Format::Gzip => {
+            let encoder = GzEncoder::new(writer, Compression::default());
+            let mut builder = tar::Builder::new(encoder);
+            builder.append_dir_all(base_name, source_dir)?;
+            builder.into_inner()?.finish()?;
+        }
+        Format::Xz => {
+            let preset = if xz_level.extreme {
+                xz_level.preset | LZMA_PRESET_EXTREME
+            } else {
+                xz_level.preset
+            };
+            let options = LzmaOptions::new_preset(preset)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            let mut filters = Filters::new();
+            filters.lzma2(&options);
+            let stream = Stream::new_stream_encoder(&filters, Check::Crc64)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+            let encoder = XzEncoder::new_stream(writer, stream);
+            let mut builder = tar::Builder::new(encoder);
+            builder.append_dir_all(base_name, source_dir)?;
+            builder.into_inner()?.finish()?;
+        }
+    }
+    Path::new(out_path)
+        .canonicalize()
+        .or_else(|_| Ok(PathBuf::from(out_path)))
+}
@@ -0,0 +1,117 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use fs_utils::copy::destination_directory;
+
+/// Preserve the source's permission bits on each copied entry.
+pub const PRESERVE_MODE: u32 = 1 << 0;
+/// Preserve the source's access and modification times on each copied entry.
+pub const PRESERVE_TIMES: u32 = 1 << 1;
+/// Recreate symlinks as symlinks instead of following them and copying
+/// their target's contents.
+pub const COPY_SYMLINKS: u32 = 1 << 2;
+
+/// Copies the contents of `source_dir` to `destination_dir`, the same as
+/// `copy_directory`, but honoring `flags`: preserving mode bits and/or
+/// access/modification times read from the source entry, and optionally
+/// recreating symlinks instead of following them. It will not perform the
+/// copy operation if the effective destination directory already exists.
+pub fn copy_directory_ex(
+    source_dir: &str,
+    destination_dir: &str,
+    flags: u32,
+) -> io::Result<PathBuf> {
+    let dest_root = destination_directory(source_dir, destination_dir);
+    if dest_root.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "effective destination directory already exists",
+        ));
+    }
+    copy_tree(Path::new(source_dir), &dest_root, flags)?;
+    Ok(dest_root)
+}
+
+fn copy_tree(source: &Path, dest: &Path, flags: u32) -> io::Result<()> {
+    let mut metadata = fs::symlink_metadata(source)?;
+    if metadata.file_type().is_symlink() && flags & COPY_SYMLINKS == 0 {
+        metadata = fs::metadata(source)?;
+    }
+    if metadata.file_type().is_symlink() {
+        let target = fs::read_link(source)?;
+        symlink(&target, dest)?;
+    } else if metadata.is_dir() {
+        fs::create_dir_all(dest)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &dest.join(entry.file_name()), flags)?;
+        }
+    } else {
+        fs::copy(source, dest)?;
+    }
+    apply_metadata(source, dest, &metadata, flags)
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    if target.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+fn apply_metadata(
+    source: &Path,
+    dest: &Path,
+    metadata: &fs::Metadata,
+    flags: u32,
+) -> io::Result<()> {
+    let is_symlink = metadata.file_type().is_symlink();
+    if flags & PRESERVE_MODE != 0 && !is_symlink {
+        fs::set_permissions(dest, metadata.permissions())?;
+    }
+    if flags & PRESERVE_TIMES != 0 {
+        let accessed = filetime::FileTime::from_last_access_time(metadata);
+        let modified = filetime::FileTime::from_last_modification_time(metadata);
+        if is_symlink {
+            filetime::set_symlink_file_times(dest, accessed, modified)?;
+        } else {
+            filetime::set_file_times(dest, accessed, modified)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the permission bits of the file or directory at `path`.
+#[cfg(unix)]
+pub fn file_mode(path: &str) -> io::Result<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = fs::metadata(path)?;
+    Ok(metadata.permissions().mode())
+}
+
+/// Reads the permission bits of the file or directory at `path`.
+///
+/// Windows has no POSIX mode bits; this synthesizes a conventional mode
+/// from the read-only and directory attributes, matching what
+/// `std::os::unix::fs::PermissionsExt` would report for an equivalent
+/// Unix file (`0o444`/`0o666` for read-only/writable, with the
+/// directory/executable bits set for directories).
+#[cfg(windows)]
+pub fn file_mode(path: &str) -> io::Result<u32> {
+    let metadata = fs::metadata(path)?;
+    let writable = !metadata.permissions().readonly();
+    let mut mode: u32 = if metadata.is_dir() { 0o40000 } else { 0o100000 };
+    mode |= if writable { 0o666 } else { 0o444 };
+    if metadata.is_dir() {
+        mode |= 0o111;
+    }
+    Ok(mode)
+}
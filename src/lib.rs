@@ -21,6 +21,7 @@
 */
 use std::os::raw::c_char;
 use std::os::raw::c_int;
+use std::os::raw::c_uint;
 use std::ffi::CString;
 use std::ffi::CStr;
 use std::vec::Vec;
@@ -32,6 +33,17 @@ use fs_utils::read::head_to_string;
 use fs_utils::read::head_to_string_with_message;
 use fs_utils::remove::cleanup_folder;
 
+mod secure_remove;
+use secure_remove::cleanup_folder_secure;
+#[cfg(windows)]
+mod windows_remove;
+mod archive;
+use archive::{archive_directory, Format as ArchiveFormat, XzLevel};
+mod copy_ex;
+use copy_ex::{copy_directory_ex, file_mode};
+mod tail;
+use tail::{tail, tail_to_string, tail_to_string_with_message};
+
 #[repr(C)]
 union fs_utils_t {
     buffer: *mut c_char,
@@ -178,6 +190,279 @@ unsafe extern "C" fn fs_utils_copy_directory(
     }
 }
 
+/// Packages the contents of the source directory into a single compressed
+/// tar archive at out_path.
+///
+/// format selects the compressor: 0 for gzip, 1 for xz.
+/// xz_level is only used when format is xz: pass 0-9 for the matching
+/// xz preset, or -1 to request the highest-ratio "best" mode (xz -9e).
+/// It is ignored for gzip.
+///
+/// Example:
+/// * *
+/// int main()
+/// {
+///   fs_utils_t fs;
+///   int res;
+///   if ((res = fs_utils_archive_directory(&fs, "src", "out.tar.xz", 1, -1)) != 0)
+///   {
+///     if (res == 1)
+///     {
+///       printf("Something went wrong: %s", fs.error);
+///       return -1;
+///     }
+///     else
+///     {
+///       printf("Something went wrong");
+///       return -1;
+///     }
+///   }
+///
+///   printf("Path: %s\n", fs.buffer);
+///   fs_utils_clean(&fs);
+///   return 0;
+/// }
+/// * *
+///
+/// @param fs_utils pointer to fs_utils_t
+/// @param source_dir
+/// @param out_path
+/// @param format 0 for gzip, 1 for xz
+/// @param xz_level 0-9, or -1 for the highest-ratio "best" mode; ignored for gzip
+/// @return 0 on success and non zero value on failure
+#[no_mangle]
+unsafe extern "C" fn fs_utils_archive_directory(
+    fs_utils: *mut fs_utils_t,
+    source_dir: *const c_char,
+    out_path: *const c_char,
+    format: c_int,
+    xz_level: c_int,
+) -> c_int {
+    if source_dir.is_null() || out_path.is_null() {
+        match CString::new("source_dir or out_path is null") {
+            Ok(s) => {
+                (*fs_utils).error = s.into_raw();
+                return 1;
+            }
+            Err(_) => {
+                (*fs_utils).error = std::ptr::null_mut();
+                return -1;
+            }
+        }
+    }
+    let sdir = match CStr::from_ptr(source_dir).to_str() {
+        Ok(s) => s,
+        Err(_) => match CString::new("UTF-8 validation failed in source_dir") {
+            Ok(s) => {
+                (*fs_utils).error = s.into_raw();
+                return 1;
+            }
+            Err(_) => {
+                (*fs_utils).error = std::ptr::null_mut();
+                return -1;
+            }
+        },
+    };
+    let opath = match CStr::from_ptr(out_path).to_str() {
+        Ok(s) => s,
+        Err(_) => match CString::new("UTF-8 validation failed in out_path") {
+            Ok(s) => {
+                (*fs_utils).error = s.into_raw();
+                return 1;
+            }
+            Err(_) => {
+                (*fs_utils).error = std::ptr::null_mut();
+                return -1;
+            }
+        },
+    };
+    let archive_format = match format {
+        0 => ArchiveFormat::Gzip,
+        1 => ArchiveFormat::Xz,
+        _ => match CString::new("format must be 0 (gzip) or 1 (xz)") {
+            Ok(s) => {
+                (*fs_utils).error = s.into_raw();
+                return 1;
+            }
+            Err(_) => {
+                (*fs_utils).error = std::ptr::null_mut();
+                return -1;
+            }
+        },
+    };
+    let level = if xz_level < 0 {
+        XzLevel::BEST
+    } else {
+        XzLevel {
+            preset: xz_level.min(9) as u32,
+            extreme: false,
+        }
+    };
+    match archive_directory(sdir, opath, archive_format, level) {
+        Ok(v) => match CString::new(v.to_string_lossy().into_owned()) {
+            Ok(s) => {
+                (*fs_utils).buffer = s.into_raw();
+                0
+            }
+            Err(err) => match CString::new(err.to_string()) {
+                Ok(s) => {
+                    (*fs_utils).error = s.into_raw();
+                    1
+                }
+                Err(_) => {
+                    (*fs_utils).error = std::ptr::null_mut();
+                    -1
+                }
+            },
+        },
+        Err(_) => -1,
+    }
+}
+
+/// Copies the contents of the source directory to the given destination
+/// directory, the same as `fs_utils_copy_directory`, but lets flags
+/// request that the source's metadata be preserved on each copied entry.
+///
+/// flags is a bitwise-OR of:
+/// - 1 (preserve permission bits)
+/// - 2 (preserve access/modification times)
+/// - 4 (recreate symlinks instead of following them)
+///
+/// Example:
+/// * *
+/// int main()
+/// {
+///   fs_utils_t fs;
+///   int res;
+///   if ((res = fs_utils_copy_directory_ex(&fs, "src", "dest", 1 | 2)) != 0)
+///   {
+///     if (res == 1)
+///     {
+///       printf("Something went wrong: %s", fs.error);
+///       return -1;
+///     }
+///     else
+///     {
+///       printf("Something went wrong");
+///       return -1;
+///     }
+///   }
+///
+///   printf("Path: %s\n", fs.buffer);
+///   fs_utils_clean(&fs);
+///   return 0;
+/// }
+/// * *
+///
+/// @param fs_utils pointer to fs_utils_t
+/// @param source_dir
+/// @param destination_dir
+/// @param flags bitwise-OR of 1 (preserve mode), 2 (preserve times), 4 (copy symlinks as symlinks)
+/// @return 0 on success and non zero value on failure
+#[no_mangle]
+unsafe extern "C" fn fs_utils_copy_directory_ex(
+    fs_utils: *mut fs_utils_t,
+    source_dir: *const c_char,
+    destination_dir: *const c_char,
+    flags: c_uint,
+) -> c_int {
+    if source_dir.is_null() || destination_dir.is_null() {
+        match CString::new("source_dir or destination_dir is null") {
+            Ok(s) => {
+                (*fs_utils).error = s.into_raw();
+                return 1;
+            }
+            Err(_) => {
+                (*fs_utils).error = std::ptr::null_mut();
+                return -1;
+            }
+        }
+    }
+    let sdir = match CStr::from_ptr(source_dir).to_str() {
+        Ok(s) => s,
+        Err(_) => match CString::new("UTF-8 validation failed in source_dir") {
+            Ok(s) => {
+                (*fs_utils).error = s.into_raw();
+                return 1;
+            }
+            Err(_) => {
+                (*fs_utils).error = std::ptr::null_mut();
+                return -1;
+            }
+        },
+    };
+    let ddir = match CStr::from_ptr(destination_dir).to_str() {
+        Ok(s) => s,
+        Err(_) => match CString::new("UTF-8 validation failed in destination_dir") {
+            Ok(s) => {
+                (*fs_utils).error = s.into_raw();
+                return 1;
+            }
+            Err(_) => {
+                (*fs_utils).error = std::ptr::null_mut();
+                return -1;
+            }
+        },
+    };
+    match copy_directory_ex(sdir, ddir, flags as u32) {
+        Ok(v) => match CString::new(v.to_string_lossy().into_owned()) {
+            Ok(s) => {
+                (*fs_utils).buffer = s.into_raw();
+                0
+            }
+            Err(err) => match CString::new(err.to_string()) {
+                Ok(s) => {
+                    (*fs_utils).error = s.into_raw();
+                    1
+                }
+                Err(_) => {
+                    (*fs_utils).error = std::ptr::null_mut();
+                    -1
+                }
+            },
+        },
+        Err(_) => -1,
+    }
+}
+
+/// Reads the permission bits of the file or directory at path.
+///
+/// Example:
+/// * *
+/// int main()
+/// {
+///   unsigned int mode;
+///   if (fs_utils_file_mode("path", &mode) != 0)
+///   {
+///     printf("Something went wrong\n");
+///     return -1;
+///   }
+///   printf("Mode: %o\n", mode);
+///   return 0;
+/// }
+/// * *
+///
+/// @param path
+/// @param mode
+/// @return 0 on success and non zero value on failure
+#[no_mangle]
+unsafe extern "C" fn fs_utils_file_mode(path: *const c_char, mode: *mut c_uint) -> c_int {
+    if path.is_null() || mode.is_null() {
+        return -1;
+    }
+    let str = match CStr::from_ptr(path).to_str() {
+        Ok(s) => s,
+        Err(_) => return -1,
+    };
+    match file_mode(str) {
+        Ok(v) => {
+            *mode = v as c_uint;
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
 /// Example:
 /// * *
 /// int main()
@@ -367,6 +652,154 @@ unsafe extern "C" fn fs_utils_head_to_string_with_message(
     }
 }
 
+/// Reads the last N bytes from a file.
+/// It is the natural complement to fs_utils_head, reading from the end
+/// of the file instead of the beginning.
+///
+/// Example:
+/// * *
+/// int main()
+/// {
+///   size_t length;
+///   uint8_t* res = fs_utils_tail("path", 10, &length);
+///   if (res == NULL)
+///   {
+///       printf("Something went wrong");
+///       return -1;
+///   }
+///   for (size_t i = 0; i < length; i++)
+///   {
+///      printf("%c", res[i]);
+///   }
+///   fs_utils_free_array(res, length);
+///   return 0;
+/// }
+/// * *
+///
+/// @param path
+/// @param limit
+/// @param length
+/// @return array
+#[no_mangle]
+unsafe extern "C" fn fs_utils_tail(
+    path: *const c_char,
+    limit: usize,
+    length: *mut usize,
+) -> *mut u8 {
+    if path.is_null() {
+        return std::ptr::null_mut();
+    }
+    let str = match CStr::from_ptr(path).to_str() {
+        Ok(s) => s,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match tail(str, limit) {
+        Ok(mut v) => {
+            v.shrink_to_fit();
+            let ptr: *mut u8 = v.as_mut_ptr();
+            *length = v.len();
+            std::mem::forget(v);
+            ptr
+        }
+        Err(_) => return std::ptr::null_mut(),
+    }
+}
+
+/// Reads the last N bytes from a file and return them as a string.
+/// It assumes that the file is encoded with UTF-8, so any invalid UTF-8
+/// sequences will be replaced with U+FFFD REPLACEMENT CHARACTER, which looks like this: �.
+///
+/// Example:
+/// * *
+/// int main()
+/// {
+///   char* res = fs_utils_tail_to_string("path", 10);
+///   if (res == NULL)
+///   {
+///       printf("Something went wrong");
+///       return -1;
+///   }
+///   printf("%s", res);
+///   fs_utils_free(res);
+///   return 0;
+/// }
+/// * *
+///
+/// @param path
+/// @param limit
+/// @return dynamic string
+#[no_mangle]
+unsafe extern "C" fn fs_utils_tail_to_string(path: *const c_char, limit: usize) -> *mut c_char {
+    if path.is_null() {
+        return std::ptr::null_mut();
+    }
+    let str = match CStr::from_ptr(path).to_str() {
+        Ok(s) => s,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    let res = match tail_to_string(str, limit) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match CString::new(res) {
+        Ok(s) => s.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Reads the last N bytes from a file and return them as a string.
+/// If the file size is greater than N bytes, the truncation message will be put at the start of the String,
+/// since it is the beginning of the file that was cut off.
+/// It assumes that the file is encoded with UTF-8, so any invalid UTF-8
+/// sequences will be replaced with U+FFFD REPLACEMENT CHARACTER, which looks like this: �.
+///
+/// Example:
+/// * *
+/// int main()
+/// {
+///   char* res = fs_utils_tail_to_string_with_message("path", 10, "Error");
+///   if (res == NULL)
+///   {
+///       printf("Something went wrong");
+///       return -1;
+///   }
+///   printf("%s", res);
+///   fs_utils_free(res);
+///   return 0;
+/// }
+/// * *
+///
+/// @param path
+/// @param limit
+/// @param truncation_message
+/// @return dynamic string
+#[no_mangle]
+unsafe extern "C" fn fs_utils_tail_to_string_with_message(
+    path: *const c_char,
+    limit: usize,
+    truncation_message: *const c_char,
+) -> *mut c_char {
+    if path.is_null() || truncation_message.is_null() {
+        return std::ptr::null_mut();
+    }
+    let str = match CStr::from_ptr(path).to_str() {
+        Ok(s) => s,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    let msg = match CStr::from_ptr(truncation_message).to_str() {
+        Ok(s) => s,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    let res = match tail_to_string_with_message(str, limit, msg) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match CString::new(res) {
+        Ok(s) => s.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
 /// Cleans up the contents (files and folders) of the given folder while keeping the folder itself.
 /// It is useful if you don't want to loose the permissions set on the folder
 /// or if you only have enough permissions to manipulate with the contents of the given folder
@@ -396,12 +829,72 @@ unsafe extern "C" fn fs_utils_cleanup_folder(folder_path: *const c_char) -> c_in
         Ok(s) => s,
         Err(_) => return -1,
     };
-    match cleanup_folder(str) {
+    // On Windows, deletion is scheduled rather than immediate and
+    // read-only files block unlinking outright, so a freshly emptied
+    // directory can fail to go away while a child is still pending
+    // deletion; the rename-before-delete implementation works around
+    // both issues where the plain path-based removal below does not.
+    #[cfg(windows)]
+    let result = windows_remove::remove_tree(str).map(|_| ());
+    #[cfg(not(windows))]
+    let result = cleanup_folder(str);
+    match result {
         Ok(_) => 0,
         Err(_) => -1,
     }
 }
 
+/// Recursively removes the contents (files and folders) of the given folder
+/// while keeping the folder itself, the same as `fs_utils_cleanup_folder`,
+/// but resolves each entry relative to an already-opened directory file
+/// descriptor instead of re-walking paths by name. This closes the
+/// symlink time-of-check/time-of-use race that a path-based recursive
+/// removal is vulnerable to on a shared or world-writable directory
+/// (CVE-2022-21658): an entry swapped for a symlink before it is opened for
+/// the descent is unlinked rather than followed.
+/// The fd-relative removal is implemented on Linux and Android. On Windows
+/// this uses the same rename-before-delete removal as
+/// `fs_utils_cleanup_folder`; on other platforms it always fails.
+///
+/// Example:
+/// * *
+/// int main()
+/// {
+///   int removed_count;
+///   if (fs_utils_cleanup_folder_secure("folder_path", &removed_count) != 0)
+///   {
+///       printf("Something went wrong");
+///       return -1;
+///   }
+///   printf("Removed %d entries\n", removed_count);
+///   return 0;
+/// }
+/// * *
+///
+/// @param folder_path
+/// @param removed_count set to the number of entries removed
+/// @return 0 on success and non zero value on failure
+#[no_mangle]
+unsafe extern "C" fn fs_utils_cleanup_folder_secure(
+    folder_path: *const c_char,
+    removed_count: *mut c_int,
+) -> c_int {
+    if folder_path.is_null() || removed_count.is_null() {
+        return -1;
+    }
+    let str = match CStr::from_ptr(folder_path).to_str() {
+        Ok(s) => s,
+        Err(_) => return -1,
+    };
+    match cleanup_folder_secure(str) {
+        Ok(count) => {
+            *removed_count = count as c_int;
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
 /// function to free the memory after using fs_utils functions
 ///
 /// @param ptr string returned from fs_utils functions
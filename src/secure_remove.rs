@@ -0,0 +1,181 @@
+use std::io;
+
+/// Recursively removes the contents of `path`, resolving every directory
+/// entry relative to an already-opened directory file descriptor instead
+/// of re-walking paths by name.
+///
+/// Each descent opens the child with `O_DIRECTORY | O_NOFOLLOW`: if the
+/// entry was swapped for a symlink between the initial open and the
+/// descent, the open fails with `ENOTDIR`/`ELOOP` and the entry is
+/// unlinked instead of followed. Because the operation is anchored to an
+/// already-opened inode rather than a re-resolved path, it is not
+/// vulnerable to the symlink time-of-check/time-of-use race fixed
+/// upstream by CVE-2022-21658.
+///
+/// The folder at `path` itself is kept, only its contents are removed.
+/// Returns the number of entries removed.
+///
+/// Only implemented on Linux and Android: enumerating a directory fd with
+/// `readdir` requires resetting `errno` to tell a real error apart from
+/// end-of-directory, which is done through the libc errno accessor of
+/// those targets (`__errno_location()` on glibc/musl, `__errno()` on
+/// Bionic).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn cleanup_folder_secure(path: &str) -> io::Result<usize> {
+    unix::remove_tree(path)
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+pub fn cleanup_folder_secure(_path: &str) -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "fs_utils_cleanup_folder_secure is only implemented on Linux/Android and Windows",
+    ))
+}
+
+/// On Windows, deletion is scheduled rather than immediate and read-only
+/// files block unlinking outright, so the fd-relative technique above
+/// doesn't apply; `fs_utils_cleanup_folder_secure` instead reuses the
+/// rename-before-delete removal in [`crate::windows_remove`] that already
+/// gives `fs_utils_cleanup_folder` reliable behavior on this platform.
+#[cfg(windows)]
+pub fn cleanup_folder_secure(path: &str) -> io::Result<usize> {
+    crate::windows_remove::remove_tree(path)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn cleanup_folder_secure(_path: &str) -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "fs_utils_cleanup_folder_secure is not implemented on this platform",
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod unix {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::io::RawFd;
+    use std::path::Path;
+
+    pub fn remove_tree(path: &str) -> io::Result<usize> {
+        let root = open_dir_nofollow(Path::new(path).as_os_str().as_bytes())?;
+        let mut removed = 0usize;
+        remove_dir_contents(root.0, &mut removed)?;
+        Ok(removed)
+    }
+
+    struct Dir(RawFd);
+
+    impl Drop for Dir {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.0);
+            }
+        }
+    }
+
+    fn to_cstring(bytes: &[u8]) -> io::Result<CString> {
+        CString::new(bytes)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a null byte"))
+    }
+
+    fn open_dir_nofollow(path: &[u8]) -> io::Result<Dir> {
+        let cpath = to_cstring(path)?;
+        let fd = unsafe {
+            libc::open(
+                cpath.as_ptr(),
+                libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Dir(fd))
+    }
+
+    fn openat_dir_nofollow(dirfd: RawFd, name: &CStr) -> io::Result<Dir> {
+        let fd = unsafe {
+            libc::openat(
+                dirfd,
+                name.as_ptr(),
+                libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Dir(fd))
+    }
+
+    fn unlinkat(dirfd: RawFd, name: &CStr, flags: libc::c_int) -> io::Result<()> {
+        let ret = unsafe { libc::unlinkat(dirfd, name.as_ptr(), flags) };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Reads the entry names of `dirfd`, skipping `.` and `..`.
+    fn read_dir_names(dirfd: RawFd) -> io::Result<Vec<CString>> {
+        let dup_fd = unsafe { libc::dup(dirfd) };
+        if dup_fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let dir = unsafe { libc::fdopendir(dup_fd) };
+        if dir.is_null() {
+            let err = io::Error::last_os_error();
+            unsafe { libc::close(dup_fd) };
+            return Err(err);
+        }
+        let mut names = Vec::new();
+        loop {
+            unsafe {
+                #[cfg(target_os = "linux")]
+                let errno = libc::__errno_location();
+                #[cfg(target_os = "android")]
+                let errno = libc::__errno();
+                *errno = 0;
+            }
+            let entry = unsafe { libc::readdir(dir) };
+            if entry.is_null() {
+                let err = io::Error::last_os_error();
+                unsafe { libc::closedir(dir) };
+                return if err.raw_os_error() == Some(0) {
+                    Ok(names)
+                } else {
+                    Err(err)
+                };
+            }
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+            let bytes = name.to_bytes();
+            if bytes == b"." || bytes == b".." {
+                continue;
+            }
+            names.push(name.to_owned());
+        }
+    }
+
+    fn remove_dir_contents(dirfd: RawFd, removed: &mut usize) -> io::Result<()> {
+        for name in read_dir_names(dirfd)? {
+            match openat_dir_nofollow(dirfd, &name) {
+                Ok(child) => {
+                    remove_dir_contents(child.0, removed)?;
+                    drop(child);
+                    unlinkat(dirfd, &name, libc::AT_REMOVEDIR)?;
+                    *removed += 1;
+                }
+                Err(err)
+                    if err.raw_os_error() == Some(libc::ENOTDIR)
+                        || err.raw_os_error() == Some(libc::ELOOP) =>
+                {
+                    unlinkat(dirfd, &name, 0)?;
+                    *removed += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
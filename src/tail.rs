@@ -0,0 +1,47 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Reads the last `limit` bytes from the file at `path`.
+///
+/// Seeks from the end of the file and reads forward, so large files are
+/// not read in full. If the file is smaller than `limit`, the whole file
+/// is returned.
+pub fn tail(path: &str, limit: usize) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let read_len = file_len.min(limit as u64);
+    file.seek(SeekFrom::End(-(read_len as i64)))?;
+    let mut buf = vec![0u8; read_len as usize];
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads the last `limit` bytes from the file at `path` and returns them
+/// as a string. It assumes that the file is encoded with UTF-8, so any
+/// invalid UTF-8 sequences will be replaced with U+FFFD REPLACEMENT
+/// CHARACTER, which looks like this: �.
+pub fn tail_to_string(path: &str, limit: usize) -> io::Result<String> {
+    let bytes = tail(path, limit)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Reads the last `limit` bytes from the file at `path` and returns them
+/// as a string. If the file size is greater than `limit` bytes, the
+/// truncation message is put at the start of the String, since it is the
+/// beginning of the file that was cut off. It assumes that the file is
+/// encoded with UTF-8, so any invalid UTF-8 sequences will be replaced
+/// with U+FFFD REPLACEMENT CHARACTER, which looks like this: �.
+pub fn tail_to_string_with_message(
+    path: &str,
+    limit: usize,
+    truncation_message: &str,
+) -> io::Result<String> {
+    let file_len = std::fs::metadata(path)?.len();
+    let bytes = tail(path, limit)?;
+    let mut result = String::new();
+    if file_len > limit as u64 {
+        result.push_str(truncation_message);
+    }
+    result.push_str(&String::from_utf8_lossy(&bytes));
+    Ok(result)
+}
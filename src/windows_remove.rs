@@ -0,0 +1,233 @@
+//! Windows-reliable recursive removal.
+//!
+//! Windows file deletion is scheduled rather than immediate: a handle kept
+//! open elsewhere (an antivirus scanner, an indexer, a caller's own open
+//! file) can make a freshly emptied directory fail to go away because a
+//! child is still pending deletion, and read-only files refuse to unlink
+//! at all. Both problems are avoided by clearing the read-only bit and
+//! then renaming every entry out of the way into the folder being cleaned
+//! under a unique name and marking it delete-on-close (a directory only
+//! after its own contents have been removed), rather than deleting it in
+//! place by its original name.
+use std::ffi::OsStr;
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::io::{FromRawHandle, OwnedHandle};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use windows_sys::Win32::Foundation::{ERROR_ALREADY_EXISTS, HANDLE, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, FindFirstFileW, FindNextFileW, GetFileAttributesW, SetFileAttributesW,
+    FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_READONLY, FILE_ATTRIBUTE_REPARSE_POINT,
+    FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_DELETE_ON_CLOSE, FILE_FLAG_OPEN_REPARSE_POINT,
+    FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING, WIN32_FIND_DATAW,
+};
+use windows_sys::Win32::Storage::FileSystem::{FileRenameInfo, SetFileInformationByHandle, DELETE};
+use windows_sys::Win32::System::Ioctl::FSCTL_GET_REPARSE_POINT;
+use windows_sys::Win32::System::IO::DeviceIoControl;
+
+static RENAME_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+pub fn remove_tree(path: &str) -> io::Result<usize> {
+    let base_dir = Path::new(path)
+        .canonicalize()
+        .unwrap_or_else(|_| Path::new(path).to_path_buf());
+    let mut removed = 0usize;
+    remove_dir_contents(Path::new(path), &base_dir, &mut removed)?;
+    Ok(removed)
+}
+
+fn to_wide(path: &Path) -> Vec<u16> {
+    OsStr::new(path.as_os_str())
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect()
+}
+
+fn open_for_delete(path: &Path, is_dir: bool, is_reparse_point: bool) -> io::Result<OwnedHandle> {
+    let wide = to_wide(path);
+    let mut flags = FILE_FLAG_DELETE_ON_CLOSE;
+    if is_dir {
+        flags |= FILE_FLAG_BACKUP_SEMANTICS;
+    }
+    if is_reparse_point {
+        flags |= FILE_FLAG_OPEN_REPARSE_POINT;
+    }
+    let handle = unsafe {
+        CreateFileW(
+            wide.as_ptr(),
+            DELETE,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            flags,
+            0,
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedHandle::from_raw_handle(handle as *mut _) })
+}
+
+fn is_reparse_point(attrs: u32) -> bool {
+    attrs & FILE_ATTRIBUTE_REPARSE_POINT != 0
+}
+
+fn open_for_query(path: &Path, is_dir: bool) -> io::Result<OwnedHandle> {
+    let wide = to_wide(path);
+    let mut flags = FILE_FLAG_OPEN_REPARSE_POINT;
+    if is_dir {
+        flags |= FILE_FLAG_BACKUP_SEMANTICS;
+    }
+    let handle = unsafe {
+        CreateFileW(
+            wide.as_ptr(),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            flags,
+            0,
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedHandle::from_raw_handle(handle as *mut _) })
+}
+
+/// Confirms, via `FSCTL_GET_REPARSE_POINT` on a handle opened with
+/// `FILE_FLAG_OPEN_REPARSE_POINT`, that `path` is a symlink or a junction
+/// (mount point) rather than trusting the `FILE_ATTRIBUTE_REPARSE_POINT`
+/// bit alone: the handle can't be redirected to the link's target the
+/// way a path-based re-check could be, and an unrecognized reparse tag
+/// gets treated as "not a symlink/junction" so it isn't mishandled as one.
+fn is_symlink_or_junction(path: &Path, is_dir: bool) -> io::Result<bool> {
+    let handle = open_for_query(path, is_dir)?;
+    let tag = reparse_tag(&handle)?;
+    Ok(tag == IO_REPARSE_TAG_SYMLINK || tag == IO_REPARSE_TAG_MOUNT_POINT)
+}
+
+/// Clears the read-only bit (if set) so the handle can later be opened
+/// with `DELETE` access, then renames the entry into `base_dir` under a
+/// unique name and marks it delete-on-close. This moves it out of its
+/// original name immediately, so a pending-deletion child can no longer
+/// block removal of its former parent by that name.
+fn rename_away_and_mark_delete(path: &Path, base_dir: &Path, is_dir: bool) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    let wide_path = to_wide(path);
+    let attrs = unsafe { GetFileAttributesW(wide_path.as_ptr()) };
+    if attrs != u32::MAX && attrs & FILE_ATTRIBUTE_READONLY != 0 {
+        unsafe { SetFileAttributesW(wide_path.as_ptr(), attrs & !FILE_ATTRIBUTE_READONLY) };
+    }
+    let reparse_point = attrs != u32::MAX
+        && is_reparse_point(attrs)
+        && is_symlink_or_junction(path, is_dir).unwrap_or(true);
+
+    let handle = open_for_delete(path, is_dir, reparse_point)?;
+
+    loop {
+        let n = RENAME_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let new_name = base_dir.join(format!(".fs_utils_deleting_{}_{}", std::process::id(), n));
+        let wide_new_name: Vec<u16> = OsStr::new(new_name.as_os_str()).encode_wide().collect();
+
+        // FILE_RENAME_INFO is a variable-length struct (a fixed header
+        // followed by the new name); build it by hand into a byte buffer
+        // since the name length isn't known until here.
+        let header_len = std::mem::size_of::<u8>() + 7 + std::mem::size_of::<HANDLE>() + 4;
+        let name_bytes_len = wide_new_name.len() * 2;
+        let mut buf = vec![0u8; header_len + name_bytes_len];
+        buf[header_len - 4..header_len].copy_from_slice(&(name_bytes_len as u32).to_ne_bytes());
+        for (i, unit) in wide_new_name.iter().enumerate() {
+            buf[header_len + i * 2..header_len + i * 2 + 2].copy_from_slice(&unit.to_ne_bytes());
+        }
+
+        let ok = unsafe {
+            SetFileInformationByHandle(
+                handle.as_raw_handle() as HANDLE,
+                FileRenameInfo,
+                buf.as_ptr() as *const _,
+                buf.len() as u32,
+            )
+        };
+        if ok != 0 {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(ERROR_ALREADY_EXISTS as i32) {
+            return Err(err);
+        }
+    }
+
+    // Dropping the handle closes it; FILE_FLAG_DELETE_ON_CLOSE then
+    // removes the entry under its new, unreachable name.
+    drop(handle);
+    Ok(())
+}
+
+fn remove_dir_contents(dir: &Path, base_dir: &Path, removed: &mut usize) -> io::Result<()> {
+    let pattern = to_wide(&dir.join("*"));
+    let mut find_data: WIN32_FIND_DATAW = unsafe { std::mem::zeroed() };
+    let find_handle = unsafe { FindFirstFileW(pattern.as_ptr(), &mut find_data) };
+    if find_handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+    let result = (|| loop {
+        let name = wide_cstr_to_string(&find_data.cFileName);
+        if name != "." && name != ".." {
+            let child = dir.join(&name);
+            let is_dir = find_data.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY != 0;
+            let reparse = is_reparse_point(find_data.dwFileAttributes);
+            if is_dir && !reparse {
+                remove_dir_contents(&child, base_dir, removed)?;
+            }
+            rename_away_and_mark_delete(&child, base_dir, is_dir)?;
+            *removed += 1;
+        }
+        if unsafe { FindNextFileW(find_handle, &mut find_data) } == 0 {
+            let err = io::Error::last_os_error();
+            return if err.raw_os_error() == Some(windows_sys::Win32::Foundation::ERROR_NO_MORE_FILES as i32)
+            {
+                Ok(())
+            } else {
+                Err(err)
+            };
+        }
+    })();
+    unsafe { windows_sys::Win32::Storage::FileSystem::FindClose(find_handle) };
+    result
+}
+
+fn wide_cstr_to_string(buf: &[u16]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+    String::from_utf16_lossy(&buf[..len])
+}
+
+const IO_REPARSE_TAG_MOUNT_POINT: u32 = 0xA000_0003;
+const IO_REPARSE_TAG_SYMLINK: u32 = 0xA000_000C;
+
+/// Reads the reparse tag of an already-open handle via
+/// `FSCTL_GET_REPARSE_POINT`.
+fn reparse_tag(handle: &OwnedHandle) -> io::Result<u32> {
+    use std::os::windows::io::AsRawHandle;
+    let mut buf = [0u8; 16 * 1024];
+    let mut bytes_returned = 0u32;
+    let ok = unsafe {
+        DeviceIoControl(
+            handle.as_raw_handle() as HANDLE,
+            FSCTL_GET_REPARSE_POINT,
+            std::ptr::null(),
+            0,
+            buf.as_mut_ptr() as *mut _,
+            buf.len() as u32,
+            &mut bytes_returned,
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]))
+}